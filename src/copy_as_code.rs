@@ -4,7 +4,7 @@ use std::fmt::Write;
 
 use crate::edit_session::EditSession;
 use crate::path::{Path, PointType};
-use druid::kurbo::{Affine, BezPath, PathEl, Shape};
+use druid::kurbo::{Affine, BezPath, PathEl, Point, Shape};
 
 /// Generates druid-compatible drawing code for all of the `Paths` in this
 /// session, if any exist.
@@ -53,6 +53,226 @@ pub fn make_glyphs_plist(session: &EditSession) -> Option<Vec<u8>> {
     Some(data)
 }
 
+/// A contour point in a `glyf`-compatible quadratic outline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyfPoint {
+    pub x: f64,
+    pub y: f64,
+    pub on_curve: bool,
+}
+
+/// A single closed contour of on-curve and off-curve points.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GlyfContour {
+    pub points: Vec<GlyfPoint>,
+}
+
+/// An error converting a [`Path`] into a glyf-compatible quadratic outline.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlyfError {
+    /// The contour didn't end with a `close_path`.
+    OpenContour,
+    /// A curve segment's points were coincident and couldn't be fit.
+    DegenerateSegment,
+    /// Fitting didn't converge to `tolerance` within the recursion limit.
+    ToleranceUnreachable,
+}
+
+impl std::fmt::Display for GlyfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GlyfError::OpenContour => write!(f, "open contour has no glyf representation"),
+            GlyfError::DegenerateSegment => write!(f, "degenerate curve segment"),
+            GlyfError::ToleranceUnreachable => write!(f, "curve fit did not converge to tolerance"),
+        }
+    }
+}
+
+impl std::error::Error for GlyfError {}
+
+/// Converts the paths in this session into `glyf`-compatible quadratic contours.
+pub fn make_glyf_contours(
+    session: &EditSession,
+    tolerance: f64,
+) -> Result<Vec<GlyfContour>, GlyfError> {
+    session
+        .paths
+        .iter()
+        .map(|path| contour_to_glyf(&path.bezier(), tolerance))
+        .collect()
+}
+
+fn contour_to_glyf(bezier: &BezPath, tolerance: f64) -> Result<GlyfContour, GlyfError> {
+    let mut points = Vec::new();
+    let mut current = Point::ZERO;
+    let mut start = Point::ZERO;
+    let mut closed = false;
+
+    for el in bezier.elements() {
+        match *el {
+            PathEl::MoveTo(p) => {
+                start = p;
+                current = p;
+                points.push(GlyfPoint { x: p.x, y: p.y, on_curve: true });
+            }
+            PathEl::LineTo(p) => {
+                points.push(GlyfPoint { x: p.x, y: p.y, on_curve: true });
+                current = p;
+            }
+            PathEl::QuadTo(ctrl, p) => {
+                points.push(GlyfPoint { x: ctrl.x, y: ctrl.y, on_curve: false });
+                points.push(GlyfPoint { x: p.x, y: p.y, on_curve: true });
+                current = p;
+            }
+            PathEl::CurveTo(p1, p2, p3) => {
+                let mut quads = Vec::new();
+                fit_cubic_to_quadratics(current, p1, p2, p3, tolerance, &mut quads)?;
+                for (ctrl, end) in quads {
+                    points.push(GlyfPoint { x: ctrl.x, y: ctrl.y, on_curve: false });
+                    points.push(GlyfPoint { x: end.x, y: end.y, on_curve: true });
+                }
+                current = p3;
+            }
+            PathEl::ClosePath => closed = true,
+        }
+    }
+
+    if !closed {
+        return Err(GlyfError::OpenContour);
+    }
+
+    // `close_path` leaves the final on-curve point coincident with the
+    // starting `moveto`; drop the duplicate.
+    if points.len() > 1 {
+        if let Some(last) = points.last() {
+            if last.on_curve && last.x == start.x && last.y == start.y {
+                points.pop();
+            }
+        }
+    }
+
+    Ok(GlyfContour { points: coalesce_implied_on_curve(points) })
+}
+
+/// Caps de Casteljau bisection depth so an unreachable `tolerance` errors
+/// out instead of recursing until the control points underflow to equal.
+const MAX_SUBDIVISION_DEPTH: u32 = 16;
+
+/// Fits the cubic `p0..p3` to one or more quadratics within `tolerance`.
+fn fit_cubic_to_quadratics(
+    p0: Point,
+    p1: Point,
+    p2: Point,
+    p3: Point,
+    tolerance: f64,
+    out: &mut Vec<(Point, Point)>,
+) -> Result<(), GlyfError> {
+    fit_cubic_to_quadratics_rec(p0, p1, p2, p3, tolerance, MAX_SUBDIVISION_DEPTH, out)
+}
+
+fn fit_cubic_to_quadratics_rec(
+    p0: Point,
+    p1: Point,
+    p2: Point,
+    p3: Point,
+    tolerance: f64,
+    depth: u32,
+    out: &mut Vec<(Point, Point)>,
+) -> Result<(), GlyfError> {
+    if p0 == p1 && p1 == p2 && p2 == p3 {
+        return Err(GlyfError::DegenerateSegment);
+    }
+
+    let q = Point::new(
+        (3.0 * p1.x - p0.x + 3.0 * p2.x - p3.x) / 4.0,
+        (3.0 * p1.y - p0.y + 3.0 * p2.y - p3.y) / 4.0,
+    );
+
+    if max_deviation(p0, p1, p2, p3, q) <= tolerance {
+        out.push((q, p3));
+        return Ok(());
+    }
+
+    if depth == 0 {
+        return Err(GlyfError::ToleranceUnreachable);
+    }
+
+    let (left, right) = subdivide_cubic(p0, p1, p2, p3);
+    fit_cubic_to_quadratics_rec(left.0, left.1, left.2, left.3, tolerance, depth - 1, out)?;
+    fit_cubic_to_quadratics_rec(right.0, right.1, right.2, right.3, tolerance, depth - 1, out)
+}
+
+/// The largest distance between the cubic and the fitted quadratic, sampled
+/// at a handful of points along both curves.
+fn max_deviation(p0: Point, p1: Point, p2: Point, p3: Point, q: Point) -> f64 {
+    const SAMPLES: usize = 8;
+    (0..=SAMPLES)
+        .map(|i| i as f64 / SAMPLES as f64)
+        .map(|t| cubic_point(p0, p1, p2, p3, t).distance(quad_point(p0, q, p3, t)))
+        .fold(0.0, f64::max)
+}
+
+fn cubic_point(p0: Point, p1: Point, p2: Point, p3: Point, t: f64) -> Point {
+    let mt = 1.0 - t;
+    Point::new(
+        mt * mt * mt * p0.x + 3.0 * mt * mt * t * p1.x + 3.0 * mt * t * t * p2.x + t * t * t * p3.x,
+        mt * mt * mt * p0.y + 3.0 * mt * mt * t * p1.y + 3.0 * mt * t * t * p2.y + t * t * t * p3.y,
+    )
+}
+
+fn quad_point(p0: Point, q: Point, p2: Point, t: f64) -> Point {
+    let mt = 1.0 - t;
+    Point::new(
+        mt * mt * p0.x + 2.0 * mt * t * q.x + t * t * p2.x,
+        mt * mt * p0.y + 2.0 * mt * t * q.y + t * t * p2.y,
+    )
+}
+
+fn subdivide_cubic(
+    p0: Point,
+    p1: Point,
+    p2: Point,
+    p3: Point,
+) -> ((Point, Point, Point, Point), (Point, Point, Point, Point)) {
+    let mid = |a: Point, b: Point| Point::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0);
+    let p01 = mid(p0, p1);
+    let p12 = mid(p1, p2);
+    let p23 = mid(p2, p3);
+    let p012 = mid(p01, p12);
+    let p123 = mid(p12, p23);
+    let p0123 = mid(p012, p123);
+    ((p0, p01, p012, p0123), (p0123, p123, p23, p3))
+}
+
+/// Drops on-curve points that sit at the midpoint of their off-curve
+/// neighbors, relying on the implied-on-curve rule to reconstruct them.
+fn coalesce_implied_on_curve(points: Vec<GlyfPoint>) -> Vec<GlyfPoint> {
+    if points.len() < 3 {
+        return points;
+    }
+
+    let mut out: Vec<GlyfPoint> = Vec::with_capacity(points.len());
+    let mut i = 0;
+    while i < points.len() {
+        let cur = points[i];
+        if cur.on_curve {
+            if let (Some(prev), Some(&next)) = (out.last(), points.get(i + 1)) {
+                if !prev.on_curve && !next.on_curve {
+                    let mid_x = (prev.x + next.x) / 2.0;
+                    let mid_y = (prev.y + next.y) / 2.0;
+                    if mid_x == cur.x && mid_y == cur.y {
+                        i += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+        out.push(cur);
+        i += 1;
+    }
+    out
+}
+
 fn append_path(path: &BezPath, out: &mut String) -> std::fmt::Result {
     out.push('\n');
     for element in path.elements() {
@@ -88,6 +308,76 @@ struct GlyphPlistPath {
     nodes: Vec<String>,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn straight_cubic_fits_in_one_quadratic() {
+        // control points on the line from p0 to p3: the cubic is really a
+        // line, so a single quadratic should fit it exactly
+        let p0 = Point::new(0.0, 0.0);
+        let p1 = Point::new(10.0, 0.0);
+        let p2 = Point::new(20.0, 0.0);
+        let p3 = Point::new(30.0, 0.0);
+        let mut quads = Vec::new();
+        fit_cubic_to_quadratics(p0, p1, p2, p3, 0.01, &mut quads).unwrap();
+        assert_eq!(quads.len(), 1);
+    }
+
+    #[test]
+    fn sharp_curve_needs_subdivision_at_tight_tolerance() {
+        let p0 = Point::new(0.0, 0.0);
+        let p1 = Point::new(0.0, 100.0);
+        let p2 = Point::new(100.0, 100.0);
+        let p3 = Point::new(100.0, 0.0);
+        let mut quads = Vec::new();
+        fit_cubic_to_quadratics(p0, p1, p2, p3, 0.01, &mut quads).unwrap();
+        assert!(quads.len() > 1);
+    }
+
+    #[test]
+    fn unreachable_tolerance_is_a_distinct_error() {
+        let p0 = Point::new(0.0, 0.0);
+        let p1 = Point::new(0.0, 100.0);
+        let p2 = Point::new(100.0, 100.0);
+        let p3 = Point::new(100.0, 0.0);
+        let mut quads = Vec::new();
+        let err = fit_cubic_to_quadratics(p0, p1, p2, p3, 0.0, &mut quads).unwrap_err();
+        assert_eq!(err, GlyfError::ToleranceUnreachable);
+    }
+
+    #[test]
+    fn degenerate_segment_is_an_error() {
+        let p = Point::new(5.0, 5.0);
+        let mut quads = Vec::new();
+        let err = fit_cubic_to_quadratics(p, p, p, p, 0.01, &mut quads).unwrap_err();
+        assert_eq!(err, GlyfError::DegenerateSegment);
+    }
+
+    #[test]
+    fn open_contour_is_an_error() {
+        let mut bez = BezPath::new();
+        bez.move_to((0.0, 0.0));
+        bez.line_to((10.0, 0.0));
+        let err = contour_to_glyf(&bez, 0.01).unwrap_err();
+        assert_eq!(err, GlyfError::OpenContour);
+    }
+
+    #[test]
+    fn closed_line_contour_round_trips_on_curve_points() {
+        let mut bez = BezPath::new();
+        bez.move_to((0.0, 0.0));
+        bez.line_to((10.0, 0.0));
+        bez.line_to((10.0, 10.0));
+        bez.line_to((0.0, 10.0));
+        bez.close_path();
+        let contour = contour_to_glyf(&bez, 0.01).unwrap();
+        assert_eq!(contour.points.len(), 4);
+        assert!(contour.points.iter().all(|p| p.on_curve));
+    }
+}
+
 impl From<&Path> for GlyphPlistPath {
     fn from(src: &Path) -> GlyphPlistPath {
         let mut next_is_curve = src
@@ -1,6 +1,9 @@
 //! A widget that draws a glyph
 
-use kurbo::{Affine, BezPath, Rect, Shape, Vec2};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use kurbo::{Affine, BezPath, PathEl, Point, Rect, Shape, Vec2};
 use norad::glyph::{Contour, ContourPoint, Glyph, PointType};
 use piet::{FillRule, FontBuilder, RenderContext, Text, TextLayout, TextLayoutBuilder};
 use piet_common::Piet;
@@ -14,16 +17,67 @@ const GLYPH_COLOR: u32 =  0x6a_6a_6a_ff;
 const HIGHLIGHT_COLOR: u32 =  0xfa_fa_fa_ff;
 const ON_CLICK_COLOR: u32 =  0x_F4_24_84_ff;
 
+/// Resolves a composite glyph's component by base glyph name.
+pub type GlyphLookup = Rc<dyn Fn(&str) -> Option<Rc<Glyph>>>;
+
+type CachedTextLayout = <Piet<'static> as RenderContext>::TextLayout;
+
+/// A double-buffered cache of text layouts, keyed by `(text, font_size)`.
+struct TextLayoutCache {
+    current: HashMap<(String, u32), CachedTextLayout>,
+    previous: HashMap<(String, u32), CachedTextLayout>,
+}
+
+impl TextLayoutCache {
+    fn new() -> Self {
+        TextLayoutCache {
+            current: HashMap::new(),
+            previous: HashMap::new(),
+        }
+    }
+
+    fn get_or_build(
+        &mut self,
+        text: &str,
+        font_size: f32,
+        build: impl FnOnce() -> CachedTextLayout,
+    ) -> CachedTextLayout {
+        let key = (text.to_string(), font_size.to_bits());
+        if let Some(layout) = self.current.get(&key) {
+            return layout.clone();
+        }
+        let layout = match self.previous.remove(&key) {
+            Some(layout) => layout,
+            None => build(),
+        };
+        self.current.insert(key, layout.clone());
+        layout
+    }
+
+    fn finish_frame(&mut self) {
+        self.previous.clear();
+        std::mem::swap(&mut self.previous, &mut self.current);
+    }
+}
+
 #[allow(dead_code)]
 pub struct GlyphWidget {
     glyph: Glyph,
     path: BezPath,
+    text_cache: TextLayoutCache,
+    fill_rule: FillRule,
 }
 
 impl GlyphWidget {
-    pub fn new(glyph: Glyph) -> Self {
-        let path = path_for_glyph(&glyph);
-        GlyphWidget { glyph, path }
+    pub fn new(glyph: Glyph, lookup: &GlyphLookup) -> Self {
+        let path = path_for_glyph(&glyph, lookup);
+        let fill_rule = default_fill_rule(&path);
+        GlyphWidget { glyph, path, text_cache: TextLayoutCache::new(), fill_rule }
+    }
+
+    /// Overrides the guessed fill rule (see [`default_fill_rule`]).
+    pub fn set_fill_rule(&mut self, fill_rule: FillRule) {
+        self.fill_rule = fill_rule;
     }
 
     pub fn ui(self, ctx: &mut Ui) -> Id {
@@ -31,6 +85,60 @@ impl GlyphWidget {
     }
 }
 
+// if every contour in a multi-contour glyph winds the same way, its
+// counters rely on even-odd overlap removal rather than opposite winding
+fn default_fill_rule(path: &BezPath) -> FillRule {
+    let mut signs = Vec::new();
+    let mut area = 0.0;
+    let mut start = Point::ZERO;
+    let mut prev = Point::ZERO;
+    let mut in_contour = false;
+
+    for el in path.elements() {
+        match *el {
+            PathEl::MoveTo(p) => {
+                if in_contour {
+                    signs.push(contour_sign(area));
+                }
+                area = 0.0;
+                start = p;
+                prev = p;
+                in_contour = true;
+            }
+            PathEl::LineTo(p) | PathEl::QuadTo(_, p) | PathEl::CurveTo(_, _, p) => {
+                area += prev.x * p.y - p.x * prev.y;
+                prev = p;
+            }
+            PathEl::ClosePath => {
+                area += prev.x * start.y - start.x * prev.y;
+                prev = start;
+            }
+        }
+    }
+    if in_contour {
+        signs.push(contour_sign(area));
+    }
+
+    let all_same_winding = signs.len() > 1
+        && signs.iter().all(|s| *s != 0.0)
+        && signs.windows(2).all(|w| w[0] == w[1]);
+
+    if all_same_winding {
+        FillRule::EvenOdd
+    } else {
+        FillRule::NonZero
+    }
+}
+
+// like f64::signum, but a zero (degenerate) area maps to 0.0 instead of 1.0
+fn contour_sign(area: f64) -> f64 {
+    if area == 0.0 {
+        0.0
+    } else {
+        area.signum()
+    }
+}
+
 impl Widget for GlyphWidget {
     fn paint(&mut self, ctx: &mut PaintCtx, geom: &Geometry) {
 
@@ -47,7 +155,7 @@ impl Widget for GlyphWidget {
 
         if is_active {
             let brush = ctx.render_ctx.solid_brush(ON_CLICK_COLOR).unwrap();
-            ctx.render_ctx.fill(rect, &brush, FillRule::NonZero);
+            ctx.render_ctx.fill(rect, &brush, self.fill_rule);
         } else {
             //NOTE: uncomment to always draw background
             //let bg_color = ctx.render_ctx.solid_brush(0x_FF_22_44_4F).unwrap();
@@ -72,7 +180,7 @@ impl Widget for GlyphWidget {
 
         let glyph_body_color = if is_active { HIGHLIGHT_COLOR } else { GLYPH_COLOR };
         let fill = ctx.render_ctx.solid_brush(glyph_body_color).unwrap();
-        ctx.render_ctx.fill(affine * &self.path, &fill, FillRule::NonZero);
+        ctx.render_ctx.fill(affine * &self.path, &fill, self.fill_rule);
 
         if is_hot {
             let outline_color = ctx.render_ctx.solid_brush(HIGHLIGHT_COLOR).unwrap();
@@ -84,7 +192,11 @@ impl Widget for GlyphWidget {
         // draw the glyph name:
         let font_size = 12.0;
         let name_color = if is_hot { HIGHLIGHT_COLOR } else { GLYPH_COLOR };
-        let text = get_text_layout(&mut ctx.render_ctx, &self.glyph.name, font_size);
+        let name = self.glyph.name.clone();
+        let render_ctx = &mut ctx.render_ctx;
+        let text = self
+            .text_cache
+            .get_or_build(&name, font_size, || get_text_layout(render_ctx, &name, font_size));
         let xpos = geom.pos.0 + (geom.size.0 - text.width() as f32) * 0.5;
         let ypos = geom.pos.1 + geom.size.1 - font_size * 0.25;
         let pos = (xpos, ypos);
@@ -92,11 +204,13 @@ impl Widget for GlyphWidget {
         //draw a semi-translucent background
         let text_bg_rect = Rect::from_origin_size((pos.0 as f64, (pos.1 - font_size * 0.75) as f64).into(), (text.width() as f64, font_size as f64).into());
         let text_bg_color = ctx.render_ctx.solid_brush(TEXT_BG_COLOR).unwrap();
-        ctx.render_ctx.fill(text_bg_rect, &text_bg_color, FillRule::NonZero);
+        ctx.render_ctx.fill(text_bg_rect, &text_bg_color, self.fill_rule);
 
         // draw the text
         let brush = ctx.render_ctx.solid_brush(name_color).unwrap();
         ctx.render_ctx.draw_text(&text, pos, &brush);
+
+        self.text_cache.finish_frame();
     }
 
     fn layout(
@@ -141,20 +255,46 @@ fn get_text_layout<'a, 'b>(piet: &'a mut Piet, text: &'b str, font_size: f32) ->
         .unwrap()
 }
 
-pub fn path_for_glyph(glyph: &Glyph) -> BezPath {
+fn midpoint(a: Vec2, b: Vec2) -> Vec2 {
+    (a + b) / 2.0
+}
+
+pub fn path_for_glyph(glyph: &Glyph, lookup: &GlyphLookup) -> BezPath {
+    let mut seen = HashSet::new();
+    seen.insert(glyph.name.to_string());
+    path_for_glyph_impl(glyph, lookup, &mut seen)
+}
+
+fn path_for_glyph_impl(glyph: &Glyph, lookup: &GlyphLookup, seen: &mut HashSet<String>) -> BezPath {
     /// An outline can have multiple contours, which correspond to subpaths
     fn add_contour(path: &mut BezPath, contour: &Contour) {
         let mut close: Option<&ContourPoint> = None;
 
         if contour.points.is_empty() { return; }
 
-        let first = &contour.points[0];
-        path.moveto((first.x as f64, first.y as f64));
-        if first.typ != PointType::Move {
-            close = Some(first);
-        }
+        // a contour made entirely of off-curve points is an all-quadratic
+        // contour that relies on TrueType's "implied on-curve point"
+        // convention: it has no explicit start point, so we synthesize one
+        // as the midpoint between the first and last off-curve points.
+        let all_off_curve = contour.points.iter().all(|p| p.typ == PointType::OffCurve);
+
+        let (start_idx, start_point) = if all_off_curve {
+            let first: Vec2 = (contour.points[0].x as f64, contour.points[0].y as f64).into();
+            let last = contour.points.last().unwrap();
+            let last: Vec2 = (last.x as f64, last.y as f64).into();
+            (0, midpoint(first, last))
+        } else {
+            let first = &contour.points[0];
+            let point: Vec2 = (first.x as f64, first.y as f64).into();
+            if first.typ != PointType::Move {
+                close = Some(first);
+            }
+            (1, point)
+        };
 
-        let mut idx = 1;
+        path.moveto(start_point);
+
+        let mut idx = start_idx;
         let mut controls = Vec::with_capacity(2);
 
         let mut add_curve = |to_point: Vec2, controls: &mut Vec<Vec2>| {
@@ -167,6 +307,20 @@ pub fn path_for_glyph(glyph: &Glyph) -> BezPath {
             controls.clear();
         };
 
+        // closes out a run of accumulated off-curve points with a chain of
+        // `quadto`s, inserting the TrueType "implied on-curve point" at the
+        // midpoint between each consecutive pair of off-curve points
+        let mut add_qcurve = |to_point: Vec2, controls: &mut Vec<Vec2>| {
+            for pair in controls.windows(2) {
+                path.quadto(pair[0], midpoint(pair[0], pair[1]));
+            }
+            match controls.last() {
+                Some(&control) => path.quadto(control, to_point),
+                None => path.lineto(to_point),
+            }
+            controls.clear();
+        };
+
         while idx < contour.points.len() {
             let next = &contour.points[idx];
             let point: Vec2 = (next.x as f64, next.y as f64).into();
@@ -177,23 +331,167 @@ pub fn path_for_glyph(glyph: &Glyph) -> BezPath {
                     add_curve(point, &mut controls);
                 }
                 PointType::Curve => add_curve(point, &mut controls),
-                PointType::QCurve => {
-                    eprintln!("TODO: handle qcurve");
-                    add_curve(point, &mut controls);
-                }
+                PointType::QCurve => add_qcurve(point, &mut controls),
                 PointType::Move => debug_assert!(false, "illegal move point in path?"),
             }
             idx += 1;
         }
 
-        if let Some(to_close) = close.take() {
-            add_curve((to_close.x as f64, to_close.y as f64).into(), &mut controls);
+        if all_off_curve {
+            // close the wraparound: the accumulated off-curve points curve
+            // back to our synthesized start point
+            add_qcurve(start_point, &mut controls);
+        } else if let Some(to_close) = close.take() {
+            let is_qcurve = to_close.typ == PointType::QCurve;
+            let to_close: Vec2 = (to_close.x as f64, to_close.y as f64).into();
+            if is_qcurve {
+                add_qcurve(to_close, &mut controls);
+            } else {
+                add_curve(to_close, &mut controls);
+            }
         }
     }
 
     let mut path = BezPath::new();
     if let Some(outline) = glyph.outline.as_ref() {
         outline.contours.iter().for_each(|c| add_contour(&mut path, c));
+
+        for component in outline.components.iter() {
+            // guard against cyclic component references
+            if !seen.insert(component.base.clone()) {
+                continue;
+            }
+            if let Some(base_glyph) = lookup(&component.base) {
+                let base_path = path_for_glyph_impl(&base_glyph, lookup, seen);
+                let transform = &component.transform;
+                let affine = Affine::new([
+                    transform.x_scale,
+                    transform.xy_scale,
+                    transform.yx_scale,
+                    transform.y_scale,
+                    transform.x_offset,
+                    transform.y_offset,
+                ]);
+                for el in (affine * base_path).elements() {
+                    path.push(*el);
+                }
+            }
+            seen.remove(&component.base);
+        }
     }
     path
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use norad::glyph::{AffineTransform, Component, Outline};
+
+    fn point(x: f64, y: f64, typ: PointType) -> ContourPoint {
+        ContourPoint::new(x, y, typ, false, None, None)
+    }
+
+    fn glyph_with_contour(contour: Contour) -> Glyph {
+        let mut glyph = Glyph::new_named("test");
+        glyph.outline = Some(Outline { contours: vec![contour], components: Vec::new() });
+        glyph
+    }
+
+    fn square_contour() -> Contour {
+        Contour::new(
+            vec![
+                point(0.0, 0.0, PointType::Line),
+                point(10.0, 0.0, PointType::Line),
+                point(10.0, 10.0, PointType::Line),
+                point(0.0, 10.0, PointType::Line),
+            ],
+            None,
+        )
+    }
+
+    fn no_lookup() -> GlyphLookup {
+        Rc::new(|_: &str| None)
+    }
+
+    #[test]
+    fn plain_cubic_curve_stays_a_curve() {
+        let contour = Contour::new(
+            vec![
+                point(0.0, 0.0, PointType::Move),
+                point(10.0, 10.0, PointType::OffCurve),
+                point(20.0, 10.0, PointType::OffCurve),
+                point(30.0, 0.0, PointType::Curve),
+            ],
+            None,
+        );
+        let glyph = glyph_with_contour(contour);
+        let path = path_for_glyph(&glyph, &no_lookup());
+        let els: Vec<_> = path.elements().to_vec();
+        assert!(matches!(els[1], PathEl::CurveTo(..)), "expected a CurveTo, got {:?}", els[1]);
+    }
+
+    #[test]
+    fn quadratic_with_implied_on_curve_point() {
+        let contour = Contour::new(
+            vec![
+                point(0.0, 0.0, PointType::Move),
+                point(10.0, 10.0, PointType::OffCurve),
+                point(20.0, 10.0, PointType::OffCurve),
+                point(30.0, 0.0, PointType::QCurve),
+            ],
+            None,
+        );
+        let glyph = glyph_with_contour(contour);
+        let path = path_for_glyph(&glyph, &no_lookup());
+        let els: Vec<_> = path.elements().to_vec();
+        // the two consecutive off-curve points should split into two
+        // quadtos joined by the implied on-curve midpoint
+        assert!(matches!(els[1], PathEl::QuadTo(..)));
+        assert!(matches!(els[2], PathEl::QuadTo(..)));
+    }
+
+    #[test]
+    fn all_off_curve_wraparound() {
+        let contour = Contour::new(
+            vec![
+                point(0.0, 10.0, PointType::OffCurve),
+                point(10.0, 0.0, PointType::OffCurve),
+                point(-10.0, 0.0, PointType::OffCurve),
+            ],
+            None,
+        );
+        let glyph = glyph_with_contour(contour);
+        let path = path_for_glyph(&glyph, &no_lookup());
+        let els: Vec<_> = path.elements().to_vec();
+        assert!(matches!(els[0], PathEl::MoveTo(..)));
+        let quad_count = els.iter().filter(|e| matches!(e, PathEl::QuadTo(..))).count();
+        assert_eq!(quad_count, 3);
+    }
+
+    #[test]
+    fn composite_glyph_resolves_and_transforms_component() {
+        let base = Rc::new(glyph_with_contour(square_contour()));
+        let lookup: GlyphLookup = Rc::new(move |name| (name == "square").then(|| base.clone()));
+
+        let mut accent = Glyph::new_named("accent");
+        accent.outline = Some(Outline {
+            contours: Vec::new(),
+            components: vec![Component {
+                base: "square".to_string(),
+                transform: AffineTransform {
+                    x_scale: 1.0,
+                    xy_scale: 0.0,
+                    yx_scale: 0.0,
+                    y_scale: 1.0,
+                    x_offset: 100.0,
+                    y_offset: 200.0,
+                },
+            }],
+        });
+
+        let path = path_for_glyph(&accent, &lookup);
+        let bbox = path.bounding_box();
+        assert_eq!((bbox.x0, bbox.y0), (100.0, 200.0));
+        assert_eq!((bbox.x1, bbox.y1), (110.0, 210.0));
+    }
+}